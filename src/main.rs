@@ -1,9 +1,15 @@
 #![feature(absolute_path)]
 
 mod matrix;
+mod namespaces;
+mod subscriptions;
 mod utils;
 mod webhook;
 
+use namespaces::{CompiledNamespaces, NamespaceConfig};
+use subscriptions::SubscriptionStore;
+use utils::{AppState, ApiSecret};
+
 use axum::{
     routing::{get, post, put},
     Router,
@@ -14,16 +20,15 @@ use ruma::api::appservice::{self, Registration};
 use serde::Serialize;
 use tracing::*;
 
+use std::collections::{HashMap, HashSet};
 use std::fs::{create_dir_all, File};
 use std::io::Write;
 use std::path::{Path as FSPath, PathBuf};
+use std::sync::{Arc, Mutex};
 
 const APPSERVICE_ID: &'static str = "matrix-free-stuff";
 const TOKEN_LENGTH: usize = 64;
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
-struct ApiSecret(pub String);
-
 #[tokio::main]
 #[instrument]
 async fn main() -> EResult<()> {
@@ -72,7 +77,7 @@ async fn main() -> EResult<()> {
                         as_token,
                         hs_token,
                         sender_localpart: "free-stuff".to_string(),
-                        namespaces: appservice::Namespaces::new(),
+                        namespaces: NamespaceConfig::from_env().to_registration_namespaces(),
                         rate_limited: None,
                         protocols: None,
                     }
@@ -118,13 +123,11 @@ async fn main() -> EResult<()> {
         }
     };
 
-    /*
     let client = ruma::client::Client::builder()
         .homeserver_url(homeserver_url)
         .access_token(Some(registration.as_token.clone()))
         .build::<ruma::client::http_client::HyperNativeTls>()
         .await?;
-    */
 
     let webhook_path = std::env::var("WEBHOOK_PATH")
         .map_err(|_| debug!("no webhook path specified"))
@@ -134,16 +137,32 @@ async fn main() -> EResult<()> {
         .map_err(|_| warn!("no secret specified"))
         .ok();
 
+    let namespaces = CompiledNamespaces::compile(&registration.namespaces);
+
+    let subscriptions_path = std::env::var("SUBSCRIPTIONS_PATH")
+        .unwrap_or_else(|_| "subscriptions.json".to_string());
+    let subscriptions = SubscriptionStore::load(subscriptions_path);
+
+    let app_state = AppState {
+        registration,
+        client,
+        secret: webhook_secret,
+        namespaces,
+        rooms: Arc::new(Mutex::new(HashSet::new())),
+        ping_transactions: Arc::new(Mutex::new(HashMap::new())),
+        transactions: Arc::new(Mutex::new(HashMap::new())),
+        subscriptions: Arc::new(Mutex::new(subscriptions)),
+    };
+
     let app = Router::new()
         .route(&webhook_path, get(webhook::handle_webhooks))
         .route(&webhook_path, post(webhook::handle_webhooks))
-        .with_state(webhook_secret)
         .route("/_matrix/app/v1/ping", post(matrix::handle_ping))
         .route(
             "/_matrix/app/v1/transactions/:transaction_id",
             put(matrix::handle_transactions),
         )
-        .with_state(registration);
+        .with_state(app_state);
 
     let addr = std::env::var("WEBHOOK_ADDR")
         .map_err(|_| debug!("no address to listen on specified"))
@@ -1,4 +1,8 @@
-use crate::utils::{AppState, ClientError, RumaError, RumaRequest, RumaResponse};
+use crate::subscriptions::Command;
+use crate::utils::{
+    send_request, AppState, ClientError, RumaClient, RumaError, RumaRequest, RumaResponse,
+    TRANSACTION_TTL,
+};
 
 use axum::{
     extract::{Path, State, TypedHeader},
@@ -9,7 +13,8 @@ use axum::{
 use hyper::Uri;
 use ruma::{
     api::appservice::{event::push_events, ping::send_ping},
-    OwnedTransactionId, RoomId,
+    events::room::message::RoomMessageEventContent,
+    OwnedRoomId, OwnedTransactionId, RoomId, TransactionId,
 };
 use tracing::*;
 
@@ -49,20 +54,36 @@ pub async fn handle_ping(
     Ok(RumaResponse(send_ping::v1::Response::new()))
 }
 
-#[instrument(skip(client, request))]
+#[instrument(skip(state, request))]
 pub async fn handle_transactions(
-    State(AppState { client, .. }): State<AppState>,
+    State(state): State<AppState>,
     Path(transaction_id): Path<OwnedTransactionId>,
     RumaRequest(request): RumaRequest<push_events::v1::Request>,
 ) -> impl IntoResponse {
+    let AppState { client, rooms, transactions, .. } = &state;
+
+    {
+        let mut transactions = transactions.lock().expect("could not get transactions");
+        transactions.retain(|_, seen_at| seen_at.elapsed() < TRANSACTION_TTL);
+
+        if transactions.contains_key(&transaction_id) {
+            debug!(?transaction_id, "transaction already processed; skipping");
+            return StatusCode::OK;
+        }
+    }
+
+    let mut succeeded = true;
+
     let mut events = request
         .events
         .into_iter()
         .filter_map(|event| event.deserialize().ok());
     while let Some(event) = events.next() {
         use ruma::{api, events::{
-            AnyStateEvent::*, AnyTimelineEvent::*, OriginalStateEvent as OSE, StateEvent::*,
+            AnyMessageLikeEvent, AnyStateEvent::*, AnyTimelineEvent::*, MessageLikeEvent,
+            OriginalMessageLikeEvent, OriginalStateEvent as OSE, StateEvent::*,
             room::member::{RoomMemberEventContent, MembershipState},
+            room::message::{MessageType, TextMessageEventContent},
         }};
 
         match event {
@@ -77,15 +98,91 @@ pub async fn handle_transactions(
                 ..
             }))) => {
                 trace!(?room_id, ?is_direct, "invited to room");
-                let id = RoomId::parse(room_id).unwrap();
-                let request = api::client::membership::join_room_by_id::v3::Request::new(id);
-                client.send_customized_request(request, |request| {
-                    // @TODO: add `via` parameter to query string with same server as inviter
-                    Ok(())
-                }).await.unwrap();
+                let id = match RoomId::parse(room_id) {
+                    Ok(id) => id,
+                    Err(err) => {
+                        error!(?err, "invalid room id in invite event; skipping");
+                        continue;
+                    }
+                };
+
+                // Only the room namespace scopes which invites the appservice
+                // acts on; the invited party here is the appservice's own
+                // `sender_localpart` user being invited into a room, not one
+                // of its namespaced ghost users.
+                if !state.namespaces_match_room(&id) {
+                    debug!(?id, "room outside of configured namespaces; ignoring invite");
+                    continue;
+                }
+
+                let mut request = api::client::membership::join_room_by_id::v3::Request::new(id.clone());
+                request.via = vec![sender.server_name().to_owned()];
+
+                match send_request(client, request).await {
+                    Some(_) => {
+                        rooms
+                            .lock()
+                            .expect("could not get joined rooms")
+                            .insert(id);
+                    }
+                    None => {
+                        error!(?id, "failed to join invited room; skipping");
+                        succeeded = false;
+                    }
+                }
+            },
+            MessageLike(AnyMessageLikeEvent::RoomMessage(MessageLikeEvent::Original(
+                OriginalMessageLikeEvent {
+                    room_id,
+                    content: RoomMessageEventContent {
+                        msgtype: MessageType::Text(TextMessageEventContent { body, .. }),
+                        ..
+                    },
+                    ..
+                },
+            ))) => {
+                if let Some(command) = Command::parse(&body) {
+                    let reply = state
+                        .subscriptions
+                        .lock()
+                        .expect("could not get subscriptions")
+                        .apply(room_id.clone(), command);
+
+                    if !send_reply(client, room_id, reply).await {
+                        succeeded = false;
+                    }
+                }
             },
             _ => debug!("unhandled event"),
         }
     }
+
+    if succeeded {
+        transactions
+            .lock()
+            .expect("could not get transactions")
+            .insert(transaction_id, Instant::now());
+    } else {
+        debug!(?transaction_id, "transaction had failures; not marking as handled");
+    }
+
     StatusCode::OK
 }
+
+/// Sends a reply, returning whether it was delivered.
+#[instrument(skip(client))]
+async fn send_reply(client: &RumaClient, room_id: OwnedRoomId, body: String) -> bool {
+    use ruma::api::client::message::send_message_event;
+
+    let content = RoomMessageEventContent::text_plain(body);
+    let request =
+        send_message_event::v3::Request::new(room_id.clone(), &TransactionId::new(), &content);
+
+    match send_request(client, request).await {
+        Some(_) => true,
+        None => {
+            error!(?room_id, "failed to send command reply");
+            false
+        }
+    }
+}
@@ -0,0 +1,151 @@
+use ruma::{api::appservice::Namespaces, RoomAliasId, RoomId, UserId};
+
+use regex::Regex;
+
+/// Precompiled counterpart of [`ruma::api::appservice::Namespaces`].
+///
+/// Each namespace regex from the [`Registration`](ruma::api::appservice::Registration)
+/// is compiled exactly once at startup and kept here so `handle_transactions`
+/// doesn't recompile a `Regex` for every incoming event.
+#[derive(Debug, Clone)]
+pub struct CompiledNamespaces {
+    users: Vec<Regex>,
+    rooms: Vec<Regex>,
+    aliases: Vec<Regex>,
+}
+
+impl CompiledNamespaces {
+    /// Compiles every namespace regex declared in the appservice `Registration`
+    /// exactly once, so the hot path in `handle_transactions` never recompiles one.
+    pub fn compile(namespaces: &Namespaces) -> Self {
+        let compile_all = |patterns: &[ruma::api::appservice::Namespace]| {
+            patterns
+                .iter()
+                .filter_map(|namespace| match Regex::new(&namespace.regex) {
+                    Ok(regex) => Some(regex),
+                    Err(err) => {
+                        tracing::error!(?err, pattern = %namespace.regex, "invalid namespace regex; ignoring");
+                        None
+                    }
+                })
+                .collect()
+        };
+
+        Self {
+            users: compile_all(&namespaces.users),
+            rooms: compile_all(&namespaces.rooms),
+            aliases: compile_all(&namespaces.aliases),
+        }
+    }
+
+    pub fn matches_user(&self, user_id: &UserId) -> bool {
+        self.users.iter().any(|regex| regex.is_match(user_id.as_str()))
+    }
+
+    pub fn matches_room(&self, room_id: &RoomId) -> bool {
+        self.rooms.iter().any(|regex| regex.is_match(room_id.as_str()))
+    }
+
+    pub fn matches_alias(&self, room_alias: &RoomAliasId) -> bool {
+        self.aliases
+            .iter()
+            .any(|regex| regex.is_match(room_alias.as_str()))
+    }
+}
+
+/// Raw namespace patterns as read from the environment, before compilation.
+#[derive(Debug, Clone, Default)]
+pub struct NamespaceConfig {
+    pub users: Vec<String>,
+    pub rooms: Vec<String>,
+    pub aliases: Vec<String>,
+}
+
+impl NamespaceConfig {
+    /// Reads `AS_NAMESPACE_USERS`, `AS_NAMESPACE_ROOMS` and `AS_NAMESPACE_ALIASES`,
+    /// each a comma-separated list of regex patterns, defaulting to empty.
+    pub fn from_env() -> Self {
+        let split = |var: &str| {
+            std::env::var(var)
+                .map(|value| {
+                    value
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|s| !s.is_empty())
+                        .map(str::to_string)
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+
+        Self {
+            users: split("AS_NAMESPACE_USERS"),
+            rooms: split("AS_NAMESPACE_ROOMS"),
+            aliases: split("AS_NAMESPACE_ALIASES"),
+        }
+    }
+
+    pub fn to_registration_namespaces(&self) -> ruma::api::appservice::Namespaces {
+        use ruma::api::appservice::Namespace;
+
+        let to_namespaces = |patterns: &[String]| {
+            patterns
+                .iter()
+                .map(|pattern| Namespace::new(true, pattern.clone()))
+                .collect()
+        };
+
+        ruma::api::appservice::Namespaces {
+            users: to_namespaces(&self.users),
+            rooms: to_namespaces(&self.rooms),
+            aliases: to_namespaces(&self.aliases),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compiled(users: &[&str], rooms: &[&str]) -> CompiledNamespaces {
+        let config = NamespaceConfig {
+            users: users.iter().map(|s| s.to_string()).collect(),
+            rooms: rooms.iter().map(|s| s.to_string()).collect(),
+            aliases: Vec::new(),
+        };
+
+        CompiledNamespaces::compile(&config.to_registration_namespaces())
+    }
+
+    #[test]
+    fn empty_namespaces_match_nothing() {
+        let namespaces = compiled(&[], &[]);
+        let user = <&UserId>::try_from("@someone:example.org").unwrap();
+        let room = <&RoomId>::try_from("!room:example.org").unwrap();
+
+        assert!(!namespaces.matches_user(user));
+        assert!(!namespaces.matches_room(room));
+    }
+
+    #[test]
+    fn matches_user_checks_configured_pattern() {
+        let namespaces = compiled(&[r"^@_freestuff_.*:example\.org$"], &[]);
+
+        let ghost = <&UserId>::try_from("@_freestuff_bot:example.org").unwrap();
+        let other = <&UserId>::try_from("@someone:example.org").unwrap();
+
+        assert!(namespaces.matches_user(ghost));
+        assert!(!namespaces.matches_user(other));
+    }
+
+    #[test]
+    fn matches_room_checks_configured_pattern() {
+        let namespaces = compiled(&[], &[r"^!freestuff-.*:example\.org$"]);
+
+        let managed = <&RoomId>::try_from("!freestuff-announcements:example.org").unwrap();
+        let other = <&RoomId>::try_from("!random:example.org").unwrap();
+
+        assert!(namespaces.matches_room(managed));
+        assert!(!namespaces.matches_room(other));
+    }
+}
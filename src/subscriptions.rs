@@ -0,0 +1,206 @@
+use ruma::OwnedRoomId;
+use serde::{Deserialize, Serialize};
+use tracing::*;
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// A room's preferences for free-game announcements.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Subscription {
+    pub subscribed: bool,
+    /// Lower-cased store names (e.g. `"steam"`, `"epic"`) to restrict
+    /// announcements to. Empty means "every store".
+    pub platforms: HashSet<String>,
+}
+
+impl Subscription {
+    pub fn wants(&self, store: &str) -> bool {
+        self.subscribed
+            && (self.platforms.is_empty() || self.platforms.contains(&store.to_lowercase()))
+    }
+}
+
+/// Persistent `RoomId` -> [`Subscription`] store, backed by a JSON file on disk.
+#[derive(Debug)]
+pub struct SubscriptionStore {
+    path: PathBuf,
+    subscriptions: HashMap<OwnedRoomId, Subscription>,
+}
+
+impl SubscriptionStore {
+    pub fn load(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref().to_path_buf();
+
+        let subscriptions = match std::fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or_else(|err| {
+                error!(?err, ?path, "failed to parse subscription store; starting empty");
+                HashMap::new()
+            }),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(err) => {
+                error!(?err, ?path, "failed to read subscription store; starting empty");
+                HashMap::new()
+            }
+        };
+
+        Self { path, subscriptions }
+    }
+
+    fn save(&self) {
+        let result = serde_json::to_vec_pretty(&self.subscriptions)
+            .map_err(|err| err.to_string())
+            .and_then(|bytes| std::fs::write(&self.path, bytes).map_err(|err| err.to_string()));
+
+        if let Err(err) = result {
+            error!(?err, path = ?self.path, "failed to persist subscription store");
+        }
+    }
+
+    pub fn get(&self, room_id: &ruma::RoomId) -> Subscription {
+        self.subscriptions.get(room_id).cloned().unwrap_or_default()
+    }
+
+    pub fn subscribe(&mut self, room_id: OwnedRoomId) {
+        self.subscriptions.entry(room_id).or_default().subscribed = true;
+        self.save();
+    }
+
+    pub fn unsubscribe(&mut self, room_id: OwnedRoomId) {
+        self.subscriptions.entry(room_id).or_default().subscribed = false;
+        self.save();
+    }
+
+    pub fn set_platforms(&mut self, room_id: OwnedRoomId, platforms: HashSet<String>) {
+        self.subscriptions.entry(room_id).or_default().platforms = platforms;
+        self.save();
+    }
+
+    /// Applies a parsed `!freestuff` command and returns the confirmation
+    /// message the bot should reply with.
+    pub fn apply(&mut self, room_id: OwnedRoomId, command: Command) -> String {
+        match command {
+            Command::Subscribe => {
+                self.subscribe(room_id);
+                "Subscribed to free game announcements.".to_string()
+            }
+            Command::Unsubscribe => {
+                self.unsubscribe(room_id);
+                "Unsubscribed from free game announcements.".to_string()
+            }
+            Command::Platforms(platforms) => {
+                let summary = if platforms.is_empty() {
+                    "all stores".to_string()
+                } else {
+                    let mut list: Vec<_> = platforms.iter().cloned().collect();
+                    list.sort();
+                    list.join(", ")
+                };
+
+                self.set_platforms(room_id, platforms);
+                format!("Now announcing free games from: {summary}")
+            }
+        }
+    }
+}
+
+/// A parsed `!freestuff` chat command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Subscribe,
+    Unsubscribe,
+    Platforms(HashSet<String>),
+}
+
+impl Command {
+    /// Parses a message body like `!freestuff platforms steam,epic`.
+    pub fn parse(body: &str) -> Option<Self> {
+        let mut words = body.split_whitespace();
+
+        if words.next()? != "!freestuff" {
+            return None;
+        }
+
+        match words.next()? {
+            "subscribe" => Some(Command::Subscribe),
+            "unsubscribe" => Some(Command::Unsubscribe),
+            "platforms" => {
+                let first = words.next()?;
+                let platforms = std::iter::once(first)
+                    .chain(words)
+                    .flat_map(|word| word.split(','))
+                    .map(|platform| platform.trim().to_lowercase())
+                    .filter(|platform| !platform.is_empty())
+                    .collect();
+
+                Some(Command::Platforms(platforms))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_requires_freestuff_prefix() {
+        assert_eq!(Command::parse("subscribe"), None);
+        assert_eq!(Command::parse("!freestuff subscribe"), Some(Command::Subscribe));
+    }
+
+    #[test]
+    fn parse_subscribe_and_unsubscribe() {
+        assert_eq!(Command::parse("!freestuff subscribe"), Some(Command::Subscribe));
+        assert_eq!(Command::parse("!freestuff unsubscribe"), Some(Command::Unsubscribe));
+    }
+
+    #[test]
+    fn parse_platforms_lower_cases_and_trims() {
+        let parsed = Command::parse("!freestuff platforms Steam, EPIC ,gog");
+        let expected = HashSet::from(["steam".to_string(), "epic".to_string(), "gog".to_string()]);
+        assert_eq!(parsed, Some(Command::Platforms(expected)));
+    }
+
+    #[test]
+    fn parse_platforms_drops_empty_entries() {
+        let parsed = Command::parse("!freestuff platforms steam,,epic");
+        let expected = HashSet::from(["steam".to_string(), "epic".to_string()]);
+        assert_eq!(parsed, Some(Command::Platforms(expected)));
+    }
+
+    #[test]
+    fn parse_rejects_unknown_subcommand() {
+        assert_eq!(Command::parse("!freestuff frobnicate"), None);
+    }
+
+    #[test]
+    fn wants_is_false_when_unsubscribed() {
+        let subscription = Subscription {
+            subscribed: false,
+            platforms: HashSet::new(),
+        };
+        assert!(!subscription.wants("steam"));
+    }
+
+    #[test]
+    fn wants_matches_any_store_when_platforms_empty() {
+        let subscription = Subscription {
+            subscribed: true,
+            platforms: HashSet::new(),
+        };
+        assert!(subscription.wants("steam"));
+        assert!(subscription.wants("epic"));
+    }
+
+    #[test]
+    fn wants_is_case_insensitive_against_stored_platforms() {
+        let subscription = Subscription {
+            subscribed: true,
+            platforms: HashSet::from(["steam".to_string()]),
+        };
+        assert!(subscription.wants("Steam"));
+        assert!(!subscription.wants("Epic"));
+    }
+}
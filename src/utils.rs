@@ -6,23 +6,26 @@ use axum::{
     BoxError,
 };
 
+use crate::namespaces::CompiledNamespaces;
+use crate::subscriptions::SubscriptionStore;
+
 pub use ruma::api::client::Error as ClientError;
 use ruma::{
     api::{
         appservice::Registration,
         client::error::{ErrorBody, ErrorKind},
-        IncomingRequest, OutgoingResponse,
+        IncomingRequest, OutgoingRequest, OutgoingResponse,
     },
-    OwnedTransactionId,
+    OwnedRoomId, OwnedTransactionId, RoomId, UserId,
 };
 
 use serde_json::json;
 use thiserror::Error;
 use tracing::*;
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, Mutex};
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 pub type RumaClient = ruma::client::Client<ruma::client::http_client::HyperNativeTls>;
 
@@ -33,7 +36,77 @@ pub struct ApiSecret(pub String);
 pub struct AppState {
     pub registration: Registration,
     pub client: RumaClient,
+    pub secret: Option<ApiSecret>,
+    pub namespaces: CompiledNamespaces,
+    /// Rooms the appservice currently participates in, used as the fan-out
+    /// target for free-game announcements.
+    pub rooms: Arc<Mutex<HashSet<OwnedRoomId>>>,
     pub ping_transactions: Arc<Mutex<HashMap<OwnedTransactionId, Instant>>>,
+    /// `/transactions/:transaction_id` IDs that have already been processed,
+    /// so homeserver retries of the same transaction aren't handled twice.
+    pub transactions: Arc<Mutex<HashMap<OwnedTransactionId, Instant>>>,
+    pub subscriptions: Arc<Mutex<SubscriptionStore>>,
+}
+
+/// How long a handled transaction ID is remembered for before it's evicted.
+pub const TRANSACTION_TTL: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+impl AppState {
+    pub fn namespaces_match_user(&self, user_id: &UserId) -> bool {
+        self.namespaces.matches_user(user_id)
+    }
+
+    pub fn namespaces_match_room(&self, room_id: &RoomId) -> bool {
+        self.namespaces.matches_room(room_id)
+    }
+}
+
+const OUTBOUND_MAX_ATTEMPTS: u32 = 4;
+const OUTBOUND_INITIAL_BACKOFF: Duration = Duration::from_millis(250);
+
+/// Whether an outbound request error is worth retrying: transport-level
+/// failures and 5xx responses are, a well-formed 4xx rejection (bad room ID,
+/// auth failure, malformed request) is permanent and should fail fast.
+fn is_transient<C>(err: &ruma::client::Error<C, ClientError>) -> bool {
+    match err {
+        ruma::client::Error::FromHttpResponse(from_http) => from_http
+            .status_code()
+            .map_or(true, |status| status.is_server_error()),
+        ruma::client::Error::Response(_) => true,
+        _ => false,
+    }
+}
+
+/// Sends a request through `client`, retrying on transient failure (5xx
+/// responses, transport errors) with exponential backoff, making at most
+/// [`OUTBOUND_MAX_ATTEMPTS`] attempts in total. Permanent 4xx rejections are
+/// returned immediately without retrying.
+///
+/// This is the one place joins, message posts and command replies funnel
+/// through, so a flaky homeserver degrades delivery instead of panicking
+/// whatever handler called it.
+pub async fn send_request<R>(client: &RumaClient, request: R) -> Option<R::IncomingResponse>
+where
+    R: OutgoingRequest + Clone,
+{
+    let mut backoff = OUTBOUND_INITIAL_BACKOFF;
+
+    for attempt in 1..=OUTBOUND_MAX_ATTEMPTS {
+        match client.send_customized_request(request.clone(), |_| Ok(())).await {
+            Ok(response) => return Some(response),
+            Err(err) if attempt < OUTBOUND_MAX_ATTEMPTS && is_transient(&err) => {
+                warn!(?err, attempt, "outbound request failed; retrying");
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+            }
+            Err(err) => {
+                error!(?err, attempt, "outbound request failed; giving up");
+                return None;
+            }
+        }
+    }
+
+    unreachable!("loop always returns before exhausting its range")
 }
 
 #[derive(Debug, Error)]
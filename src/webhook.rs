@@ -1,4 +1,5 @@
-use crate::utils::ApiSecret;
+use crate::subscriptions::Subscription;
+use crate::utils::{send_request, AppState, RumaClient};
 
 use axum::{
     extract::State,
@@ -6,11 +7,14 @@ use axum::{
     response::{IntoResponse, Response},
     Json,
 };
-use freestuffapi::api::GameId;
+use freestuffapi::api::{Api, Game, GameId};
+use ruma::{api::client::message::send_message_event, events::room::message::RoomMessageEventContent, OwnedRoomId, TransactionId};
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json::Value as JsonValue;
 use tracing::*;
 
+use std::collections::HashMap;
+
 #[derive(Debug, PartialEq, Deserialize, Serialize)]
 pub struct Event {
     #[serde(rename = "event")]
@@ -44,7 +48,7 @@ impl IntoResponse for EventError {
 
 #[instrument(skip_all)]
 pub async fn handle_webhooks(
-    State(secret): State<Option<ApiSecret>>,
+    State(AppState { client, secret, rooms, subscriptions, .. }): State<AppState>,
     Json(event): Json<Event>,
 ) -> Result<impl IntoResponse, EventError> {
     let secret = secret.map(|s| s.0);
@@ -65,8 +69,21 @@ pub async fn handle_webhooks(
 
     match event.name.as_str() {
         "free_games" => {
-            let games = handler_data_from_json_value(event.data)?;
-            Ok(hook_free_games(games).await.into_response())
+            let ids = handler_data_from_json_value(event.data)?;
+            let rooms = rooms.lock().expect("could not get joined rooms").clone();
+
+            // Snapshot subscriptions up front so the lock isn't held across awaits.
+            let subscriptions: HashMap<OwnedRoomId, Subscription> = {
+                let store = subscriptions.lock().expect("could not get subscriptions");
+                rooms
+                    .iter()
+                    .map(|room_id| (room_id.clone(), store.get(room_id)))
+                    .collect()
+            };
+
+            Ok(hook_free_games(&client, &subscriptions, ids)
+                .await
+                .into_response())
         }
         name => {
             error!(event = name, "invalid event");
@@ -83,7 +100,95 @@ fn handler_data_from_json_value<T: DeserializeOwned>(value: JsonValue) -> Result
     })
 }
 
-#[instrument]
-async fn hook_free_games(games: Vec<GameId>) -> StatusCode {
+#[instrument(skip(client, subscriptions))]
+async fn hook_free_games(
+    client: &RumaClient,
+    subscriptions: &HashMap<OwnedRoomId, Subscription>,
+    ids: Vec<GameId>,
+) -> StatusCode {
+    let games = resolve_games(ids).await;
+
+    for game in games {
+        let content = format_announcement(&game);
+
+        for (room_id, subscription) in subscriptions {
+            if !subscription.wants(&game.store) {
+                continue;
+            }
+
+            let request = send_message_event::v3::Request::new(
+                room_id.clone(),
+                &TransactionId::new(),
+                &content,
+            );
+
+            match send_request(client, request).await {
+                Some(_) => trace!(?room_id, game = %game.title, "posted free game announcement"),
+                None => error!(?room_id, game = %game.title, "failed to post announcement"),
+            }
+        }
+    }
+
     StatusCode::OK
 }
+
+/// Fetches full metadata for each game ID from the FreeStuff API, caching
+/// results so repeated IDs within the same batch aren't re-fetched.
+#[instrument(skip_all)]
+async fn resolve_games(ids: Vec<GameId>) -> Vec<Game> {
+    let api = Api::new();
+    let mut cache: HashMap<GameId, Game> = HashMap::new();
+    let mut games = Vec::with_capacity(ids.len());
+
+    for id in ids {
+        if let Some(game) = cache.get(&id) {
+            games.push(game.clone());
+            continue;
+        }
+
+        match api.game(id.clone()).await {
+            Ok(game) => {
+                cache.insert(id, game.clone());
+                games.push(game);
+            }
+            Err(err) => error!(?err, ?id, "failed to resolve game metadata"),
+        }
+    }
+
+    games
+}
+
+fn format_announcement(game: &Game) -> RoomMessageEventContent {
+    let plain = format!(
+        "🎉 {title} is free on {store}! {original} -> {discounted} — claim it at {url}",
+        title = game.title,
+        store = game.store,
+        original = game.price.original,
+        discounted = game.price.discounted,
+        url = game.url,
+    );
+
+    let html = format!(
+        "🎉 <strong>{title}</strong> is free on {store}!<br>\
+         <s>{original}</s> &rarr; <strong>{discounted}</strong><br>\
+         <a href=\"{url}\">Claim it here</a>",
+        title = escape_html(&game.title),
+        store = escape_html(&game.store),
+        original = escape_html(&game.price.original.to_string()),
+        discounted = escape_html(&game.price.discounted.to_string()),
+        url = escape_html(&game.url),
+    );
+
+    RoomMessageEventContent::text_html(plain, html)
+}
+
+/// Escapes text for safe interpolation into the `formatted_body` HTML so a
+/// game title containing `&`, `<`, `>` or `"` can't produce malformed markup.
+fn escape_html(input: &str) -> String {
+    input
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}